@@ -0,0 +1,104 @@
+//! A generic rewrite/substitution primitive for nested interned structures,
+//! inspired by chalk-ir's folder and `subst` machinery.
+
+use std::collections::HashMap;
+use std::hash::BuildHasher;
+
+use crate::interner::Interner;
+use crate::token::{Internable, Tok};
+
+/// Marker for leaf types [Foldable] treats as atomic rather than recursing
+/// into. This has to be an explicit opt-in rather than a blanket
+/// `T: Internable` bound: a `Vec<Tok<U, Bh>>` itself satisfies `Internable`,
+/// so a blanket leaf impl would structurally overlap with the `Tok<Vec<Tok<U,
+/// Bh>>, Bh>` impl below and Rust's coherence checker would reject both
+/// (E0119) - it can't use the fact that no type is ever both a leaf and a
+/// `Vec<Tok<_>>` to tell them apart. Implement this for your own leaf types
+/// with `impl FoldLeaf for MyType {}`.
+pub trait FoldLeaf: Internable {}
+impl FoldLeaf for String {}
+
+/// A value built out of interned leaves that can be rewritten leaf-by-leaf,
+/// automatically re-interning the result. Implemented for [Tok] of a
+/// [FoldLeaf] and for [Tok] of a vector of tokens, so it composes through the
+/// same `Tok<Vec<Tok<T>>>` shape the rest of the crate builds around.
+///
+/// `Bh` is the [BuildHasher] of the [Interner] the fold re-interns through;
+/// it defaults to the crate's usual hasher.
+pub trait Foldable<Bh: BuildHasher = hashbrown::DefaultHashBuilder>: Sized {
+  /// The leaf token type `f` is applied to.
+  type Leaf: Internable;
+  /// Rewrite every leaf reachable from `self` with `f`, which maps each leaf
+  /// to a leaf of the same type. A rebuilt vector is re-interned through
+  /// `interner` rather than through `self`'s own
+  /// [TypedInterner](crate::instance::TypedInterner); a bare leaf is returned
+  /// as `f` produced it.
+  fn fold(
+    &self,
+    interner: &Interner<Bh>,
+    f: &mut impl FnMut(Tok<Self::Leaf, Bh>) -> Tok<Self::Leaf, Bh>,
+  ) -> Self;
+}
+
+impl<T: FoldLeaf, Bh: BuildHasher + Clone + Send + Sync + 'static> Foldable<Bh> for Tok<T, Bh> {
+  type Leaf = T;
+  fn fold(&self, _interner: &Interner<Bh>, f: &mut impl FnMut(Tok<T, Bh>) -> Tok<T, Bh>) -> Self {
+    f(self.clone())
+  }
+}
+
+impl<T: Internable, Bh: BuildHasher + Clone + Send + Sync + 'static> Foldable<Bh> for Tok<Vec<Tok<T, Bh>>, Bh> {
+  type Leaf = T;
+  fn fold(&self, interner: &Interner<Bh>, f: &mut impl FnMut(Tok<T, Bh>) -> Tok<T, Bh>) -> Self {
+    self.map_interned(interner, f)
+  }
+}
+
+/// A map from leaf tokens to their replacement, as used by [substitute].
+/// [Tok]'s [Hash]/[Eq] are based on its immutable `seq`/pointer identity, not
+/// on the interior mutability of the [TypedInterner](crate::instance::TypedInterner)
+/// it holds a reference to, so using it as a map key is sound despite what
+/// clippy's `mutable_key_type` lint assumes.
+#[allow(clippy::mutable_key_type)]
+pub type LeafMap<T, Bh> = HashMap<Tok<T, Bh>, Tok<T, Bh>>;
+
+/// Replace every leaf token found in `map`'s keys throughout `value` with the
+/// corresponding value, leaving anything not in `map` untouched. Re-interns
+/// through `interner`, which must be the one `value` was itself interned
+/// through.
+#[must_use]
+#[allow(clippy::mutable_key_type)]
+pub fn substitute<Bh: BuildHasher + Clone + Send + Sync + 'static, F: Foldable<Bh>>(
+  value: &F,
+  interner: &Interner<Bh>,
+  map: &LeafMap<F::Leaf, Bh>,
+) -> F {
+  value.fold(interner, &mut |t| map.get(&t).cloned().unwrap_or(t))
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::instance::Interner;
+
+  #[test]
+  #[allow(clippy::mutable_key_type)]
+  fn substitute_replaces_matching_leaves() {
+    let interner = Interner::new();
+    let a = interner.i("a");
+    let c = interner.i("c");
+    let list = interner.iv(["a".to_string(), "b".to_string(), "a".to_string()]);
+    let mut map: LeafMap<String, _> = HashMap::new();
+    map.insert(a, c);
+    let replaced = substitute(&list, &interner, &map);
+    assert_eq!(replaced.ev(), vec!["c".to_string(), "b".to_string(), "c".to_string()]);
+  }
+
+  #[test]
+  fn substitute_with_empty_map_is_identity() {
+    let interner = Interner::new();
+    let list = interner.iv(["a".to_string(), "b".to_string()]);
+    let replaced = substitute(&list, &interner, &LeafMap::new());
+    assert_eq!(replaced, list);
+  }
+}
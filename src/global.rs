@@ -1,10 +1,10 @@
 use std::borrow::Borrow;
-use std::hash::Hash;
+use std::hash::{BuildHasher, Hash};
 use std::sync::OnceLock;
 
 use crate::interner::Interner;
 use crate::token::Internable;
-use crate::Tok;
+use crate::{IdTok, Tok};
 
 static SINGLETON: OnceLock<&'static Interner> = OnceLock::new();
 
@@ -48,10 +48,25 @@ where
   get_global().i(q)
 }
 
+/// Intern something with the global interner, returning a [Copy] handle into
+/// the per-type slab instead of a [Tok]. Use [upgrade] to get the value back.
+#[must_use]
+pub fn id<Q>(q: &Q) -> IdTok<Q::Owned>
+where
+  Q: ?Sized + Eq + Hash + ToOwned,
+  Q::Owned: Borrow<Q> + Internable,
+{
+  get_global().id(q)
+}
+
+/// Resolve a handle obtained from [id] back into a full [Tok].
+#[must_use]
+pub fn upgrade<T: Internable>(handle: IdTok<T>) -> Option<Tok<T>> { get_global().upgrade(handle) }
+
 /// Fully resolve a list of interned things. If the list is interned, use
 /// [Tok::ev]
 #[must_use]
-pub fn ev<'a, T: Internable>(s: impl IntoIterator<Item = &'a Tok<T>>) -> Vec<T> {
+pub fn ev<'a, T: Internable, Bh: BuildHasher + 'a>(s: impl IntoIterator<Item = &'a Tok<T, Bh>>) -> Vec<T> {
   s.into_iter().map(|t| (**t).clone()).collect()
 }
 
@@ -102,4 +117,12 @@ mod test {
     assert_eq!(a1, a2);
     assert_ne!(a1, b);
   }
+
+  #[test]
+  pub fn id_upgrade() {
+    use super::{id, upgrade};
+    let tok = i("globally_id_upgradeable");
+    let handle = id("globally_id_upgradeable");
+    assert_eq!(upgrade(handle).unwrap(), tok);
+  }
 }
@@ -1,12 +1,12 @@
 use std::any::{Any, TypeId};
 use std::borrow::Borrow;
-use std::hash::Hash;
+use std::hash::{BuildHasher, Hash};
 use std::ops::DerefMut;
 use std::sync::{Arc, Mutex};
 
 use hashbrown::HashMap;
 
-use super::token::Tok;
+use super::token::{IdTok, Tok};
 use super::typed_interner::TypedInterner;
 use crate::token::Internable;
 
@@ -17,7 +17,7 @@ pub trait AnyInterner: Send + Sync {
   fn sweep(&self) -> usize;
 }
 
-impl<T: Internable> AnyInterner for TypedInterner<T> {
+impl<T: Internable, Bh: BuildHasher + Clone + Send + Sync + 'static> AnyInterner for TypedInterner<T, Bh> {
   fn as_any_arc(self: Arc<Self>) -> Arc<dyn Any + Send + Sync> { self }
   fn sweep(&self) -> usize { TypedInterner::sweep(self) }
 }
@@ -25,26 +25,59 @@ impl<T: Internable> AnyInterner for TypedInterner<T> {
 /// A collection of interners based on their type. Can be used to intern any
 /// object that implements [ToOwned]. Objects of the same type are stored
 /// together in a [TypedInterner]
-pub struct Interner {
+///
+/// `Bh` is the [BuildHasher] installed on every [TypedInterner] this creates;
+/// it defaults to the crate's usual hasher. Use [Interner::with_hasher] to
+/// install a faster one across the board.
+pub struct Interner<Bh = hashbrown::DefaultHashBuilder> {
   interners: Mutex<HashMap<TypeId, Arc<dyn AnyInterner>>>,
+  hasher: Bh,
 }
 impl Interner {
-  /// Create a new interner
+  /// Create a new interner using the default hasher
   #[must_use]
-  pub fn new() -> Self { Self { interners: Mutex::new(HashMap::new()) } }
+  pub fn new() -> Self { Self::with_hasher(hashbrown::DefaultHashBuilder::default()) }
+}
+impl<Bh: BuildHasher + Clone + Send + Sync + 'static> Interner<Bh> {
+  /// Create a new interner that installs `hasher` on every per-type
+  /// [TypedInterner] it creates
+  #[must_use]
+  pub fn with_hasher(hasher: Bh) -> Self { Self { interners: Mutex::new(HashMap::new()), hasher } }
 
   /// Intern something
   #[must_use]
-  pub fn i<Q>(&self, q: &Q) -> Tok<Q::Owned>
+  pub fn i<Q>(&self, q: &Q) -> Tok<Q::Owned, Bh>
   where
     Q: ?Sized + Eq + Hash + ToOwned,
     Q::Owned: Internable + Borrow<Q>,
   {
     let mut interners = self.interners.lock().unwrap();
-    let interner = get_interner(&mut interners);
+    let interner = get_interner(&mut interners, &self.hasher);
     interner.i(q)
   }
 
+  /// Intern something, returning a [Copy] handle into the per-type slab
+  /// instead of a [Tok]. Use [Interner::upgrade] to get the value back.
+  #[must_use]
+  pub fn id<Q>(&self, q: &Q) -> IdTok<Q::Owned>
+  where
+    Q: ?Sized + Eq + Hash + ToOwned,
+    Q::Owned: Internable + Borrow<Q>,
+  {
+    let mut interners = self.interners.lock().unwrap();
+    let interner = get_interner(&mut interners, &self.hasher);
+    interner.id(q)
+  }
+
+  /// Resolve a handle obtained from [Interner::id] back into a full [Tok].
+  /// See [TypedInterner::upgrade] for when this returns [None].
+  #[must_use]
+  pub fn upgrade<T: Internable>(&self, handle: IdTok<T>) -> Option<Tok<T, Bh>> {
+    let mut interners = self.interners.lock().unwrap();
+    let interner = get_interner::<T, Bh>(&mut interners, &self.hasher);
+    interner.upgrade(handle)
+  }
+
   /// Sweep values of a specific type. Useful if you just
   /// constructed a large number of values of a specific type, otherwise use
   /// [Interner::sweep]
@@ -64,7 +97,7 @@ impl Interner {
   pub fn iv<T: Internable>(
     &self,
     s: impl IntoIterator<Item = T>,
-  ) -> Tok<Vec<Tok<T>>> {
+  ) -> Tok<Vec<Tok<T, Bh>>, Bh> {
     self.i(&s.into_iter().map(|t| self.i(&t)).collect::<Vec<_>>())
   }
 
@@ -72,7 +105,7 @@ impl Interner {
   pub fn ibv<'a, Q>(
     &self,
     s: impl IntoIterator<Item = &'a Q>,
-  ) -> Tok<Vec<Tok<Q::Owned>>>
+  ) -> Tok<Vec<Tok<Q::Owned, Bh>>, Bh>
   where
     Q: ?Sized + Eq + Hash + ToOwned + 'a,
     Q::Owned: Internable,
@@ -87,13 +120,14 @@ impl Default for Interner {
 
 /// Get or create an interner for a given type
 #[must_use]
-fn get_interner<T: Internable>(
+fn get_interner<T: Internable, Bh: BuildHasher + Clone + Send + Sync + 'static>(
   interners: &mut impl DerefMut<Target = HashMap<TypeId, Arc<dyn AnyInterner>>>,
-) -> Arc<TypedInterner<T>> {
+  hasher: &Bh,
+) -> Arc<TypedInterner<T, Bh>> {
   let boxed = interners
     .raw_entry_mut()
     .from_key(&TypeId::of::<T>())
-    .or_insert_with(|| (TypeId::of::<T>(), TypedInterner::<T>::new()))
+    .or_insert_with(|| (TypeId::of::<T>(), TypedInterner::<T, Bh>::with_hasher(hasher.clone())))
     .1
     .clone();
   (Arc::downcast(boxed.as_any_arc()))
@@ -127,4 +161,32 @@ mod test {
     let key2 = interner.ibv(vec!["a", "b", "c"]);
     assert_eq!(key1, key2);
   }
+
+  #[test]
+  pub fn test_id_upgrade() {
+    let interner = Interner::new();
+    let tok = interner.i("foo");
+    let handle = interner.id("foo");
+    assert_eq!(interner.upgrade(handle).unwrap(), tok);
+  }
+
+  #[test]
+  pub fn test_custom_hasher() {
+    type Fnv = std::hash::BuildHasherDefault<std::collections::hash_map::DefaultHasher>;
+    let interner = Interner::with_hasher(Fnv::default());
+    let key1 = interner.i("foo");
+    let key2 = interner.i(&"foo".to_string());
+    assert_eq!(key1, key2);
+  }
+
+  #[test]
+  pub fn test_custom_hasher_lists_and_sweep() {
+    type Fnv = std::hash::BuildHasherDefault<std::collections::hash_map::DefaultHasher>;
+    let interner = Interner::with_hasher(Fnv::default());
+    let key1 = interner.iv(["a".to_string(), "b".to_string()]);
+    let key2 = interner.ibv(vec!["a", "b"]);
+    assert_eq!(key1, key2);
+    drop((key1, key2));
+    assert!(interner.sweep() > 0);
+  }
 }
@@ -49,14 +49,20 @@
 //! The functions exposed by this crate have short and not very descriptive
 //! names, which may seem like a red flag. In a typical use case, these
 //! functions would appear everywhere in the codebase, so this is not a concern.
+mod fold;
 mod global;
 #[warn(unsafe_code)]
 mod interner;
+#[cfg(feature = "serde")]
+mod snapshot;
 mod token;
 mod typed_interner;
 
-pub use global::{ev, i, ibv, iv, sweep, sweep_t, get_global, set_global};
-pub use token::{Internable, Tok};
+pub use fold::{substitute, Foldable, FoldLeaf};
+pub use global::{ev, i, ibv, id, iv, sweep, sweep_t, upgrade, get_global, set_global};
+#[cfg(feature = "serde")]
+pub use snapshot::{Graphed, GraphedLeaf, Snapshot};
+pub use token::{IdTok, Internable, Tok};
 
 pub mod instance {
   //! The interner uses weak references and can be cleared with [sweep], but if
@@ -0,0 +1,177 @@
+//! Structure-preserving, deduplicated serde encoding for interned graphs.
+//!
+//! The transparent [Serialize](serde::Serialize)/[Deserialize](serde::Deserialize)
+//! impls on [Tok] re-emit the full value every time a token appears, so a
+//! `Tok<Vec<Tok<String>>>` with repeated strings writes every string out in
+//! full and the sharing is lost on round-trip. [Snapshot] instead walks a
+//! token tree once, assigns each distinct token of each type a dense index,
+//! and serializes a table of unique values plus the structure as indices into
+//! that table, mirroring rustc's `Interned(usize)` representation.
+//!
+//! ```
+//! # #[cfg(feature = "serde")]
+//! # {
+//! use intern_all::{iv, Snapshot};
+//!
+//! let before = iv(["a".to_string(), "a".to_string(), "b".to_string()]);
+//! let packed = serde_json::to_string(&Snapshot::new(before.clone())).unwrap();
+//! // the table only contains the two distinct strings, not three
+//! let after: Snapshot<_> = serde_json::from_str(&packed).unwrap();
+//! assert_eq!(before, after.into_inner());
+//! # }
+//! ```
+
+use std::collections::HashMap;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::interner::Interner;
+use crate::token::{Internable, Tok};
+
+/// Marker for leaf types [Graphed] records directly in the table, as opposed
+/// to a `Vec<Tok<_>>`, which [Graphed] instead records as a row of indices
+/// into its elements' own table. This has to be an explicit opt-in rather
+/// than a blanket `T: Internable + Serialize + DeserializeOwned` bound: a
+/// `Vec<Tok<U>>` itself satisfies that bound, so a blanket leaf impl would
+/// structurally overlap with the `Tok<Vec<Tok<U>>>` impl below and Rust's
+/// coherence checker would reject both (E0119). Implement this for your own
+/// leaf types with `impl GraphedLeaf for MyType {}`.
+pub trait GraphedLeaf: Internable + Serialize + DeserializeOwned {}
+impl GraphedLeaf for String {}
+
+/// A value whose interned structure [Snapshot] knows how to deduplicate.
+/// Implemented for [Tok] of a [GraphedLeaf], and for [Tok] of a `Vec` of
+/// [Graphed] tokens, so it composes through the same `Tok<Vec<Tok<T>>>` shape
+/// the rest of the crate builds around.
+pub trait Graphed: Sized {
+  /// The deduplicated table this value's entries are recorded into.
+  type Table: Serialize + DeserializeOwned + Default;
+  /// Scratch space [Graphed::push] uses to find an existing entry for a
+  /// value in O(1) instead of scanning `Table`. Built fresh for every
+  /// [Snapshot] serialization and discarded afterwards, so it doesn't need to
+  /// be (de)serializable.
+  type Index: Default;
+  /// Record `self`, and everything it references, into `table`. Returns the
+  /// index of `self`'s own entry, reusing an existing one (tracked via
+  /// `index`) if already present.
+  fn push(&self, table: &mut Self::Table, index: &mut Self::Index) -> u32;
+  /// Look up the value at `index`, re-interning everything through `interner`.
+  fn pull(index: u32, table: &Self::Table, interner: &Interner) -> Self;
+}
+
+impl<T: GraphedLeaf> Graphed for Tok<T> {
+  type Table = Vec<T>;
+  type Index = HashMap<Tok<T>, u32>;
+
+  fn push(&self, table: &mut Self::Table, index: &mut Self::Index) -> u32 {
+    *index.entry(self.clone()).or_insert_with(|| {
+      table.push((**self).clone());
+      (table.len() - 1) as u32
+    })
+  }
+
+  fn pull(index: u32, table: &Self::Table, interner: &Interner) -> Self {
+    interner.i(&table[index as usize])
+  }
+}
+
+impl<T: Internable> Graphed for Tok<Vec<Tok<T>>>
+where Tok<T>: Graphed
+{
+  type Table = (<Tok<T> as Graphed>::Table, Vec<Vec<u32>>);
+  type Index = (<Tok<T> as Graphed>::Index, HashMap<Tok<Vec<Tok<T>>>, u32>);
+
+  fn push(&self, table: &mut Self::Table, index: &mut Self::Index) -> u32 {
+    let (elements, rows) = table;
+    let (elem_index, row_index) = index;
+    *row_index.entry(self.clone()).or_insert_with(|| {
+      let row: Vec<u32> = self.iter().map(|t| t.push(elements, elem_index)).collect();
+      rows.push(row);
+      (rows.len() - 1) as u32
+    })
+  }
+
+  fn pull(index: u32, table: &Self::Table, interner: &Interner) -> Self {
+    let (elements, rows) = table;
+    let values: Vec<Tok<T>> =
+      rows[index as usize].iter().map(|&i| Tok::<T>::pull(i, elements, interner)).collect();
+    interner.i(&values[..])
+  }
+}
+
+/// The on-the-wire shape of a [Snapshot]: the deduplicated table, and the
+/// index of the root value within it.
+#[derive(Serialize, Deserialize)]
+struct Wire<Table> {
+  table: Table,
+  root: u32,
+}
+
+/// A wrapper that serializes an interned value (and everything it
+/// transitively references) as a deduplicated table instead of inlining the
+/// full value at every occurrence. See the [module docs](self) for details.
+pub struct Snapshot<T: Graphed> {
+  root: T,
+}
+impl<T: Graphed> Snapshot<T> {
+  /// Wrap a value for structure-preserving serialization
+  #[must_use]
+  pub fn new(root: T) -> Self { Self { root } }
+
+  /// Unwrap the snapshotted value
+  #[must_use]
+  pub fn into_inner(self) -> T { self.root }
+
+  /// Deserialize a snapshot, re-interning every table entry through `interner`
+  /// instead of the global singleton. See also the [Deserialize] impl, which
+  /// uses [crate::get_global].
+  pub fn deserialize_in<'de, D>(deserializer: D, interner: &Interner) -> Result<Self, D::Error>
+  where D: serde::Deserializer<'de> {
+    let wire = Wire::<T::Table>::deserialize(deserializer)?;
+    Ok(Self { root: T::pull(wire.root, &wire.table, interner) })
+  }
+}
+
+impl<T: Graphed> std::ops::Deref for Snapshot<T> {
+  type Target = T;
+  fn deref(&self) -> &T { &self.root }
+}
+
+impl<T: Graphed> Serialize for Snapshot<T> {
+  fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+  where S: serde::Serializer {
+    let mut table = T::Table::default();
+    let mut index = T::Index::default();
+    let root = self.root.push(&mut table, &mut index);
+    Wire { table, root }.serialize(serializer)
+  }
+}
+
+impl<'de, T: Graphed> Deserialize<'de> for Snapshot<T> {
+  fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+  where D: serde::Deserializer<'de> {
+    Self::deserialize_in(deserializer, crate::get_global())
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use crate::instance::Interner;
+  use crate::{Snapshot, Tok};
+
+  #[test]
+  fn snapshot_dedups_repeated_strings() {
+    let interner = Interner::new();
+    let before = interner.iv(["a".to_string(), "a".to_string(), "b".to_string()]);
+    let packed = serde_json::to_string(&Snapshot::new(before.clone())).unwrap();
+    let (table, _): (Vec<String>, Vec<Vec<u32>>) = serde_json::from_value(
+      serde_json::from_str::<serde_json::Value>(&packed).unwrap()["table"].clone(),
+    )
+    .unwrap();
+    assert_eq!(table.len(), 2);
+    let after: Snapshot<Tok<Vec<Tok<String>>>> =
+      Snapshot::deserialize_in(&mut serde_json::Deserializer::from_str(&packed), &interner).unwrap();
+    assert_eq!(before, after.into_inner());
+  }
+}
@@ -1,13 +1,13 @@
 use std::borrow::Borrow;
-use std::hash::{Hash, Hasher};
-use std::num::NonZeroUsize;
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::marker::PhantomData;
+use std::num::{NonZeroU32, NonZeroUsize};
 use std::ops::Deref;
 use std::sync::{Arc, Weak};
 use std::{cmp, fmt};
 
 use trait_set::trait_set;
 
-#[allow(unused)] // for doc
 use super::interner::Interner;
 use super::typed_interner::TypedInterner;
 use crate::global::{self, ev};
@@ -17,7 +17,16 @@ trait_set! {
 }
 
 /// A shared instance. Equality comparison costs two pointer comparisons.
-/// Ordering is by pointer value.
+/// Ordering and hashing are based on a sequence number assigned when the
+/// value was first interned, not on the allocation address, so they stay the
+/// same across runs regardless of allocator or ASLR behaviour. Use
+/// [Tok::cmp_by_content] if you need an ordering that's stable even across
+/// differing intern orders.
+///
+/// The second type parameter is the [BuildHasher] used by the [TypedInterner]
+/// that owns this token; it defaults to the crate's usual hasher, so this
+/// only needs spelling out when interning through a [TypedInterner] created
+/// with [TypedInterner::with_hasher].
 ///
 /// # Panics
 ///
@@ -28,15 +37,16 @@ trait_set! {
 /// is only possible if an [Interner] or [TypedInterner] was constructed besides
 /// the singleton.
 #[derive(Clone)]
-pub struct Tok<T: Internable> {
+pub struct Tok<T: Internable, Bh = hashbrown::DefaultHashBuilder> {
   data: Arc<T>,
-  interner: Arc<TypedInterner<T>>,
+  interner: Arc<TypedInterner<T, Bh>>,
+  seq: u64,
 }
-impl<T: Internable> Tok<T> {
+impl<T: Internable, Bh> Tok<T, Bh> {
   /// Create a new token. Used exclusively by the interner
   #[must_use]
-  pub(crate) fn new(data: Arc<T>, interner: Arc<TypedInterner<T>>) -> Self {
-    Self { data, interner }
+  pub(crate) fn new(data: Arc<T>, interner: Arc<TypedInterner<T, Bh>>, seq: u64) -> Self {
+    Self { data, interner, seq }
   }
   /// The pointer value of the token. If this is equal, equality comparison
   /// succeeds.
@@ -54,13 +64,33 @@ impl<T: Internable> Tok<T> {
   /// Cast into usize
   #[must_use]
   pub fn usize(&self) -> usize { self.id().into() }
+  /// The order in which this value was first interned relative to other
+  /// values of the same type in the same interner. This is what [Ord] and
+  /// [Hash] for [Tok] are based on.
+  #[must_use]
+  pub fn seq(&self) -> u64 { self.seq }
   /// Panic if the two tokens weren't created with the same interner
   pub fn assert_comparable(&self, other: &Self) {
     assert_eq!(self.interner_id(), other.interner_id(), "Tokens must come from the same interner");
   }
   /// Get the typed interner that owns this token.
-  pub fn interner(&self) -> Arc<TypedInterner<T>> { self.interner.clone() }
+  pub fn interner(&self) -> Arc<TypedInterner<T, Bh>> { self.interner.clone() }
+  /// Clone the underlying `Arc`. Used exclusively by the interner, e.g. to
+  /// let an [IdTok]'s slab slot share the same allocation as the [Tok] for
+  /// the same content.
+  pub(crate) fn data(&self) -> Arc<T> { self.data.clone() }
+}
 
+impl<T: Internable + Ord, Bh> Tok<T, Bh> {
+  /// Compare by content rather than by intern order. Unlike [Ord], this gives
+  /// the same result regardless of which order the values were interned in,
+  /// which matters if you need it to agree across different runs or
+  /// interners.
+  #[must_use]
+  pub fn cmp_by_content(&self, other: &Self) -> cmp::Ordering { (**self).cmp(&**other) }
+}
+
+impl<T: Internable> Tok<T> {
   pub fn i<Q>(q: &Q) -> Self
   where
     Q: ?Sized + Eq + Hash + ToOwned<Owned = T>,
@@ -70,79 +100,150 @@ impl<T: Internable> Tok<T> {
   }
 }
 
-impl<T: Internable> Tok<Vec<Tok<T>>> {
+impl<T: Internable, Bh: BuildHasher + Clone + Send + Sync + 'static> Tok<Vec<Tok<T, Bh>>, Bh> {
   /// Extern all elements of the vector in a new vector. If the vector itself
   /// isn't interned, use [ev]
   pub fn ev(&self) -> Vec<T> { ev(&self[..]) }
 }
 
-impl<T: Internable> Tok<Vec<Tok<T>>> {
+impl<T: Internable, Bh: BuildHasher + Clone + Send + Sync + 'static> Tok<Vec<Tok<T, Bh>>, Bh> {
   /// Add a suffix to the interned vector
-  pub fn append(&self, suffix: impl IntoIterator<Item = Tok<T>>) -> Self {
+  pub fn append(&self, suffix: impl IntoIterator<Item = Tok<T, Bh>>) -> Self {
     let i = self.interner();
     i.i(&self.iter().cloned().chain(suffix).collect::<Vec<_>>())
   }
 
   /// Add a prefix to the interned vector
-  pub fn prepend(&self, prefix: impl IntoIterator<Item = Tok<T>>) -> Self {
+  pub fn prepend(&self, prefix: impl IntoIterator<Item = Tok<T, Bh>>) -> Self {
     let i = self.interner();
     i.i(&prefix.into_iter().chain(self.iter().cloned()).collect::<Vec<_>>())
   }
 }
 
-impl<T: Internable> Deref for Tok<T> {
+impl<T: Internable, Bh: BuildHasher + Clone + Send + Sync + 'static> Tok<Vec<Tok<T, Bh>>, Bh> {
+  /// Map every element through `f`, re-interning the result through
+  /// `interner` so the output is itself a single deduplicated
+  /// `Tok<Vec<Tok<U>>>`. Unlike [Tok::append]/[Tok::prepend], the element
+  /// type can change, so the caller has to supply the [Interner] to intern
+  /// the result through - `self`'s own [TypedInterner] only ever holds
+  /// `Vec<Tok<T>>`s, not `Vec<Tok<U>>`s.
+  pub fn map_interned<U: Internable>(
+    &self,
+    interner: &Interner<Bh>,
+    mut f: impl FnMut(Tok<T, Bh>) -> Tok<U, Bh>,
+  ) -> Tok<Vec<Tok<U, Bh>>, Bh> {
+    let mapped: Vec<Tok<U, Bh>> = self.iter().cloned().map(&mut f).collect();
+    interner.i(&mapped[..])
+  }
+}
+
+impl<T: Internable, Bh> Deref for Tok<T, Bh> {
   type Target = T;
 
   fn deref(&self) -> &Self::Target { self.data.as_ref() }
 }
 
-impl<T: Internable + fmt::Debug> fmt::Debug for Tok<T> {
+impl<T: Internable + fmt::Debug, Bh> fmt::Debug for Tok<T, Bh> {
   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
     write!(f, "Token({} -> {:?})", self.id(), self.data.as_ref())
   }
 }
 
-impl<T: Internable + fmt::Display> fmt::Display for Tok<T> {
+impl<T: Internable + fmt::Display, Bh> fmt::Display for Tok<T, Bh> {
   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { write!(f, "{}", **self) }
 }
 
-impl<T: Internable> Eq for Tok<T> {}
-impl<T: Internable> cmp::PartialEq for Tok<T> {
+impl<T: Internable, Bh> Eq for Tok<T, Bh> {}
+impl<T: Internable, Bh> cmp::PartialEq for Tok<T, Bh> {
   fn eq(&self, other: &Self) -> bool {
     self.assert_comparable(other);
     self.id() == other.id()
   }
 }
 
-impl<T: Internable> cmp::Ord for Tok<T> {
+impl<T: Internable, Bh> cmp::Ord for Tok<T, Bh> {
   fn cmp(&self, other: &Self) -> cmp::Ordering {
     self.assert_comparable(other);
-    self.id().cmp(&other.id())
+    self.seq.cmp(&other.seq)
   }
 }
-impl<T: Internable> cmp::PartialOrd for Tok<T> {
+impl<T: Internable, Bh> cmp::PartialOrd for Tok<T, Bh> {
   fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> { Some(self.cmp(other)) }
 }
 
-impl<T: Internable> Hash for Tok<T> {
-  fn hash<H: Hasher>(&self, state: &mut H) { state.write_usize(self.usize()) }
+impl<T: Internable, Bh> Hash for Tok<T, Bh> {
+  fn hash<H: Hasher>(&self, state: &mut H) { state.write_u64(self.seq) }
 }
 
-pub struct WeakTok<T: Internable> {
+pub struct WeakTok<T: Internable, Bh = hashbrown::DefaultHashBuilder> {
   data: Weak<T>,
-  interner: Weak<TypedInterner<T>>,
+  interner: Weak<TypedInterner<T, Bh>>,
+  seq: u64,
 }
-impl<T: Internable> WeakTok<T> {
-  pub fn new(tok: &Tok<T>) -> Self {
-    Self { data: Arc::downgrade(&tok.data), interner: Arc::downgrade(&tok.interner) }
+impl<T: Internable, Bh> WeakTok<T, Bh> {
+  pub fn new(tok: &Tok<T, Bh>) -> Self {
+    Self { data: Arc::downgrade(&tok.data), interner: Arc::downgrade(&tok.interner), seq: tok.seq }
   }
-  pub fn upgrade(&self) -> Option<Tok<T>> {
-    Some(Tok { data: self.data.upgrade()?, interner: self.interner.upgrade()? })
+  pub fn upgrade(&self) -> Option<Tok<T, Bh>> {
+    Some(Tok { data: self.data.upgrade()?, interner: self.interner.upgrade()?, seq: self.seq })
+  }
+}
+
+/// A [Copy] handle into a [TypedInterner]'s generational slab. Unlike [Tok],
+/// this doesn't keep the interned value alive and doesn't carry a pointer, so
+/// it's just 12 bytes and can be passed around and compared by value freely.
+/// Call [TypedInterner::upgrade] to resolve it back into a [Tok]; this fails
+/// once the value has been swept since the handle was issued, whether or not
+/// the slot has been reused for something else.
+pub struct IdTok<T: Internable> {
+  tag: u32,
+  index: NonZeroU32,
+  generation: u32,
+  _marker: PhantomData<fn() -> T>,
+}
+impl<T: Internable> IdTok<T> {
+  /// Create a new handle. Used exclusively by the interner
+  pub(crate) fn new(slot_index: u32, generation: u32, tag: u32) -> Self {
+    let index = NonZeroU32::new(slot_index + 1).expect("slot_index + 1 cannot be 0");
+    Self { tag, index, generation, _marker: PhantomData }
+  }
+  /// The tag of the interner that created this handle. If two handles have
+  /// different tags, comparing them panics.
+  pub(crate) fn tag(&self) -> u32 { self.tag }
+  /// The slab index this handle points to.
+  pub(crate) fn slot_index(&self) -> usize { (self.index.get() - 1) as usize }
+  /// The generation the handle was issued for.
+  pub(crate) fn generation(&self) -> u32 { self.generation }
+  /// Panic if the two handles weren't created with the same interner
+  pub fn assert_comparable(&self, other: &Self) {
+    assert_eq!(self.tag, other.tag, "IdToks must come from the same interner");
+  }
+}
+impl<T: Internable> Clone for IdTok<T> {
+  fn clone(&self) -> Self { *self }
+}
+impl<T: Internable> Copy for IdTok<T> {}
+impl<T: Internable> Eq for IdTok<T> {}
+impl<T: Internable> PartialEq for IdTok<T> {
+  fn eq(&self, other: &Self) -> bool {
+    self.assert_comparable(other);
+    (self.index, self.generation) == (other.index, other.generation)
+  }
+}
+impl<T: Internable> Hash for IdTok<T> {
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    self.index.hash(state);
+    self.generation.hash(state);
+  }
+}
+impl<T: Internable> fmt::Debug for IdTok<T> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "IdTok({}, gen {})", self.index, self.generation)
   }
 }
 
 #[cfg(feature = "serde")]
-impl<T: serde::Serialize + Internable> serde::Serialize for Tok<T> {
+impl<T: serde::Serialize + Internable, Bh> serde::Serialize for Tok<T, Bh> {
   fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
   where
     S: serde::Serializer,
@@ -160,3 +261,27 @@ impl<'a, T: serde::Deserialize<'a> + Internable> serde::Deserialize<'a> for Tok<
     T::deserialize(deserializer).map(|t| crate::i(&t))
   }
 }
+
+#[cfg(test)]
+mod test {
+  use crate::instance::Interner;
+
+  #[test]
+  fn ord_and_hash_track_intern_sequence_not_pointer() {
+    let interner = Interner::new();
+    let a = interner.i("a_seq_order");
+    let b = interner.i("b_seq_order");
+    let c = interner.i("c_seq_order");
+    assert!(a < b && b < c);
+    assert_eq!(b.seq(), a.seq() + 1);
+    assert_eq!(c.seq(), b.seq() + 1);
+  }
+
+  #[test]
+  fn cmp_by_content_ignores_intern_order() {
+    let interner = Interner::new();
+    let z = interner.i("z_content_order");
+    let a = interner.i("a_content_order");
+    assert_eq!(z.cmp_by_content(&a), std::cmp::Ordering::Greater);
+  }
+}
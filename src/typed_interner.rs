@@ -1,55 +1,251 @@
 use std::borrow::Borrow;
-use std::hash::{BuildHasher, Hash};
-use std::sync::{Arc, RwLock};
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock, Weak};
 
 use hashbrown::HashMap;
 
-use super::token::{Tok, WeakTok};
+use super::token::{IdTok, Tok, WeakTok};
 use crate::token::Internable;
 
+/// A `tokens` map key wrapping a [Weak] rather than the strong `Arc<T>`
+/// itself, so the map's own entry can't keep a value alive (see `tokens`
+/// below). Hashes by upgrading to the live content, same as `T` itself would,
+/// so it lands in the bucket a lookup by content expects; once the weak ref
+/// has died this degrades to a constant hash, which only affects already-dead
+/// entries awaiting [TypedInterner::sweep] and is never relied on for
+/// equality.
+struct WeakKey<T>(Weak<T>);
+impl<T: Hash> Hash for WeakKey<T> {
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    if let Some(v) = self.0.upgrade() {
+      v.hash(state);
+    }
+  }
+}
+
+/// One slot of the generational slab backing [IdTok]. A slot starts out
+/// holding the interned value; [TypedInterner::sweep] clears it and bumps
+/// the generation once nothing but the slab references the value, so that
+/// stale [IdTok]s can tell they've outlived their slot. `seq` is the sequence
+/// number the value was originally interned under, preserved across sweeps so
+/// that [TypedInterner::upgrade] can hand it back unchanged.
+struct Slot<T: Internable> {
+  generation: u32,
+  seq: u64,
+  value: Option<Arc<T>>,
+}
+
+static NEXT_TAG: AtomicU32 = AtomicU32::new(0);
+
 /// An interner for any type that implements [Borrow]. Not many optimizations
 /// are employed and the interner uses the default allocator. This and the use
 /// of weak references means that a long-lived instance can be kept around with
 /// regular calls to [TypedInterner::sweep].
-pub struct TypedInterner<T: Internable> {
-  tokens: RwLock<HashMap<Arc<T>, WeakTok<T>>>,
+///
+/// `Bh` is the [BuildHasher] used to hash interned values; it defaults to the
+/// crate's usual hasher. The slab's internal index always uses the default. Interning is hot and the content hashes involved are
+/// never adversarial in typical compiler-style workloads, so
+/// [TypedInterner::with_hasher] lets callers install a faster, non-DoS-resistant
+/// hasher for a meaningful throughput win.
+pub struct TypedInterner<T: Internable, Bh = hashbrown::DefaultHashBuilder> {
+  /// Keyed on a [Weak] rather than the strong `Arc<T>` itself: keeping a
+  /// strong ref in the map key would mean an entry's value can never be
+  /// observed as unreferenced (its own key would always keep it alive), so
+  /// [TypedInterner::sweep] would never be able to reclaim anything.
+  tokens: RwLock<HashMap<WeakKey<T>, WeakTok<T, Bh>, Bh>>,
+  slab: RwLock<Vec<Slot<T>>>,
+  /// Maps a [Tok::seq] value, already deduplicated by [TypedInterner::i], to
+  /// its slot in `slab`. Keying on the sequence number rather than the
+  /// content's pointer means an [IdTok] always points at the very same
+  /// `Arc` a [Tok] for the same content would, so the two handle types
+  /// never disagree about identity - and, unlike a raw pointer, a `seq` is
+  /// never reused once assigned, so a slot freed by [TypedInterner::sweep]
+  /// and then reallocated at the same address can't be mistaken for the one
+  /// it replaced.
+  by_value: RwLock<HashMap<u64, u32>>,
+  free_slots: Mutex<Vec<u32>>,
+  tag: u32,
+  /// Assigns each distinct value a sequence number at first-intern time, so
+  /// [Tok]'s [Ord]/[Hash] don't depend on allocation addresses.
+  seq: AtomicU64,
 }
 impl<T: Internable> TypedInterner<T> {
-  /// Create a fresh interner instance
+  /// Create a fresh interner instance using the default hasher
+  #[must_use]
+  pub fn new() -> Arc<Self> { Self::with_hasher(hashbrown::DefaultHashBuilder::default()) }
+}
+impl<T: Internable, Bh: BuildHasher + Clone> TypedInterner<T, Bh> {
+  /// Create a fresh interner instance that hashes interned values with `hasher`
   #[must_use]
-  pub fn new() -> Arc<Self> { Arc::new(Self { tokens: RwLock::new(HashMap::new()) }) }
+  pub fn with_hasher(hasher: Bh) -> Arc<Self> {
+    Arc::new(Self {
+      tokens: RwLock::new(HashMap::with_hasher(hasher)),
+      slab: RwLock::new(Vec::new()),
+      by_value: RwLock::new(HashMap::new()),
+      free_slots: Mutex::new(Vec::new()),
+      tag: NEXT_TAG.fetch_add(1, Ordering::Relaxed),
+      seq: AtomicU64::new(0),
+    })
+  }
 
   /// Get the number of stored values
   pub fn size(self: &Arc<Self>) -> usize { self.tokens.read().unwrap().len() }
 
   /// Remove entries which are no longer referenced anywhere else
   pub fn sweep(&self) -> usize {
-    (self.tokens.write().unwrap()).extract_if(|_, v| v.upgrade().is_none()).count()
+    let mut swept = (self.tokens.write().unwrap()).extract_if(|_, v| v.upgrade().is_none()).count();
+    let mut slab = self.slab.write().unwrap();
+    let mut free_slots = self.free_slots.lock().unwrap();
+    (self.by_value.write().unwrap()).retain(|_, &mut index| {
+      let slot = &mut slab[index as usize];
+      // `slot.value` itself is a strong reference, so a count of 1 means
+      // nothing outside the slab is holding onto it any more.
+      if slot.value.as_ref().is_some_and(|v| Arc::strong_count(v) == 1) {
+        slot.value = None;
+        slot.generation = slot.generation.wrapping_add(1);
+        free_slots.push(index);
+        swept += 1;
+        false
+      } else {
+        true
+      }
+    });
+    swept
   }
 
   /// Intern an object, returning a token
   #[must_use]
-  pub fn i<Q>(self: &Arc<Self>, q: &Q) -> Tok<T>
+  pub fn i<Q>(self: &Arc<Self>, q: &Q) -> Tok<T, Bh>
   where
     Q: ?Sized + Eq + Hash + ToOwned<Owned = T>,
     T: Borrow<Q>,
   {
     let mut tokens = self.tokens.write().unwrap();
     let hash = tokens.hasher().hash_one(q);
-    let mut ret: Option<Tok<T>> = None;
+    let mut ret: Option<Tok<T, Bh>> = None;
     tokens
       .raw_entry_mut()
-      .from_hash(hash, |k| <T as Borrow<Q>>::borrow(k) == q)
+      .from_hash(hash, |k| k.0.upgrade().is_some_and(|v| <T as Borrow<Q>>::borrow(&v) == q))
       .and_replace_entry_with(|_, v| {
         ret = Some((v.upgrade()?).clone());
         Some(v)
       })
       .or_insert_with(|| {
         let keyrc = Arc::new(q.to_owned());
-        let token = Tok::<T>::new(keyrc.clone(), self.clone());
+        let seq = self.seq.fetch_add(1, Ordering::Relaxed);
+        let token = Tok::<T, Bh>::new(keyrc.clone(), self.clone(), seq);
         ret = Some(token.clone());
-        (keyrc, WeakTok::new(&token))
+        (WeakKey(Arc::downgrade(&keyrc)), WeakTok::new(&token))
       });
     ret.expect("One of the above callbacks must have ran")
   }
+
+  /// Intern an object, returning a [Copy] handle into the generational slab
+  /// instead of a [Tok]. Use [TypedInterner::upgrade] to get the value back.
+  ///
+  /// This goes through [TypedInterner::i] first, so the handle always points
+  /// at the same deduplicated value a [Tok] for the same content would -
+  /// interning `x` once via [TypedInterner::i] and once via
+  /// [TypedInterner::id] never allocates two separate copies of `x`.
+  #[must_use]
+  pub fn id<Q>(self: &Arc<Self>, q: &Q) -> IdTok<T>
+  where
+    Q: ?Sized + Eq + Hash + ToOwned<Owned = T>,
+    T: Borrow<Q>,
+  {
+    let tok = self.i(q);
+    let seq = tok.seq();
+    let mut by_value = self.by_value.write().unwrap();
+    let index = *by_value.entry(seq).or_insert_with(|| {
+      let mut slab = self.slab.write().unwrap();
+      let slot = self.free_slots.lock().unwrap().pop().unwrap_or(slab.len() as u32);
+      if slot as usize == slab.len() {
+        slab.push(Slot { generation: 0, seq, value: Some(tok.data()) });
+      } else {
+        slab[slot as usize].seq = seq;
+        slab[slot as usize].value = Some(tok.data());
+      }
+      slot
+    });
+    let generation = self.slab.read().unwrap()[index as usize].generation;
+    IdTok::new(index, generation, self.tag)
+  }
+
+  /// Resolve an [IdTok] back into a full [Tok], provided its slot hasn't been
+  /// swept since it was issued.
+  #[must_use]
+  pub fn upgrade(self: &Arc<Self>, handle: IdTok<T>) -> Option<Tok<T, Bh>> {
+    assert_eq!(handle.tag(), self.tag, "IdTok must be resolved against the interner that created it");
+    let (value, seq) = {
+      let slab = self.slab.read().unwrap();
+      let slot = slab.get(handle.slot_index())?;
+      if slot.generation != handle.generation() {
+        return None;
+      }
+      (slot.value.clone()?, slot.seq)
+    };
+    Some(Tok::new(value, self.clone(), seq))
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn id_agrees_with_tok_for_same_content() {
+    let interner = TypedInterner::<String>::new();
+    let tok = interner.i("foo");
+    let handle = interner.id("foo");
+    assert_eq!(interner.upgrade(handle).unwrap(), tok);
+  }
+
+  #[test]
+  fn id_dedupes_like_i() {
+    let interner = TypedInterner::<String>::new();
+    let a = interner.id("foo");
+    let b = interner.id(&"foo".to_string());
+    assert_eq!(a, b);
+  }
+
+  #[test]
+  fn sweep_reuses_slot_and_bumps_generation() {
+    let interner = TypedInterner::<String>::new();
+    let stale = interner.id("foo");
+    assert!(interner.upgrade(stale).is_some());
+    interner.sweep();
+    assert!(interner.upgrade(stale).is_none());
+    let fresh = interner.id("bar");
+    assert!(interner.upgrade(fresh).is_some());
+    assert_ne!(stale.generation(), fresh.generation());
+  }
+
+  #[test]
+  #[should_panic(expected = "IdTok must be resolved against the interner that created it")]
+  fn upgrade_panics_across_interners() {
+    let a = TypedInterner::<String>::new();
+    let b = TypedInterner::<String>::new();
+    let handle = a.id("foo");
+    let _ = b.upgrade(handle);
+  }
+
+  #[test]
+  fn custom_hasher_dedupes_like_default() {
+    type Fnv = std::hash::BuildHasherDefault<std::collections::hash_map::DefaultHasher>;
+    let interner = TypedInterner::<String, Fnv>::with_hasher(Fnv::default());
+    let a = interner.i("foo");
+    let b = interner.i(&"foo".to_string());
+    assert_eq!(a, b);
+  }
+
+  #[test]
+  fn custom_hasher_sweep_and_id_still_work() {
+    type Fnv = std::hash::BuildHasherDefault<std::collections::hash_map::DefaultHasher>;
+    let interner = TypedInterner::<String, Fnv>::with_hasher(Fnv::default());
+    let handle = interner.id("foo");
+    assert!(interner.upgrade(handle).is_some());
+    interner.sweep();
+    assert!(interner.upgrade(handle).is_none());
+  }
 }